@@ -25,52 +25,147 @@
 //! drop(pool);
 //! ```
 
-use std::alloc::{alloc, dealloc, handle_alloc_error, realloc, Layout};
+#![feature(allocator_api)]
+
+use std::alloc::{handle_alloc_error, Allocator, Global, Layout};
 use std::fmt;
 use std::mem;
 use std::ops::{Deref, DerefMut};
 use std::ptr::{self, NonNull};
+use std::sync::atomic::{self, AtomicUsize, Ordering};
 use std::sync::Mutex;
 
 /// A pool of byte slices, that reuses memory.
+///
+/// Memory is bucketed into size classes. An allocation request is rounded up to the
+/// smallest class that fits, and blocks returned to the pool are kept on that class's
+/// free list so later requests of a similar, but not necessarily identical, size can
+/// still reuse them.
+///
+/// The pool is generic over the backing [`Allocator`], defaulting to [`Global`], so it
+/// can be layered on top of arena, slab, or NUMA-aware allocators.
+#[derive(Debug)]
+pub struct BytePool<A: Allocator = Global> {
+    // sorted ascending by `capacity`
+    classes: Vec<SizeClass<A>>,
+    alloc: A,
+    limits: Limits,
+    // total bytes currently sitting in all of `classes`' free lists
+    retained_bytes: AtomicUsize,
+}
+
 #[derive(Debug)]
-pub struct BytePool {
-    list: Mutex<Vec<RawBlock>>,
+struct SizeClass<A: Allocator> {
+    capacity: usize,
+    free: Mutex<Vec<RawBlock<A>>>,
+}
+
+/// Caps on how much memory a [`BytePool`] is willing to retain in its free lists.
+///
+/// By default both caps are `None`, meaning the pool retains every block handed back to
+/// one of its size classes. Blocks whose size matches no class are always deallocated.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// Maximum number of bytes retained across all size classes. Once reached, blocks
+    /// from the largest non-empty classes are evicted (deallocated) to make room, and
+    /// if that still isn't enough the returned block is deallocated instead of kept.
+    pub max_bytes: Option<usize>,
+    /// Maximum number of blocks retained per size class. Once a class is at this limit,
+    /// blocks returned to it are deallocated instead of kept.
+    pub max_blocks_per_class: Option<usize>,
+}
+
+/// The size classes (in bytes) used by [`BytePool::new`].
+///
+/// Chosen as a spread of powers of two, which keeps internal fragmentation bounded
+/// while giving variable-size workloads many chances to hit the free list.
+const DEFAULT_CLASSES: &[usize] = &[
+    64,
+    128,
+    256,
+    512,
+    1024,
+    2 * 1024,
+    4 * 1024,
+    8 * 1024,
+    16 * 1024,
+    32 * 1024,
+    64 * 1024,
+    128 * 1024,
+    256 * 1024,
+    512 * 1024,
+    1024 * 1024,
+];
+
+/// Error returned when the allocator is unable to satisfy a memory request.
+///
+/// This mirrors the information `std::alloc::handle_alloc_error` would otherwise
+/// abort the process with, letting the caller decide how to recover.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct AllocError {
+    layout: Layout,
+}
+
+impl AllocError {
+    /// The `Layout` that the allocator failed to provide memory for.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+}
+
+impl fmt::Debug for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AllocError").field("layout", &self.layout).finish()
+    }
 }
 
-pub struct RawBlock {
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "memory allocation of {} bytes failed",
+            self.layout.size()
+        )
+    }
+}
+
+impl std::error::Error for AllocError {}
+
+pub struct RawBlock<A: Allocator = Global> {
     ptr: NonNull<u8>,
     layout: Layout,
+    alloc: A,
 }
 
-unsafe impl Sync for RawBlock {}
-unsafe impl Send for RawBlock {}
+unsafe impl<A: Allocator + Send> Send for RawBlock<A> {}
+unsafe impl<A: Allocator + Sync> Sync for RawBlock<A> {}
 
 #[cfg(feature = "stable_deref")]
-unsafe impl stable_deref_trait::StableDeref for RawBlock {}
-
-pub struct Block<'a> {
-    data: mem::ManuallyDrop<RawBlock>,
-    pool: &'a BytePool,
+unsafe impl<A: Allocator> stable_deref_trait::StableDeref for RawBlock<A> {}
+
+pub struct Block<'a, A: Allocator + Clone = Global> {
+    data: mem::ManuallyDrop<RawBlock<A>>,
+    // the length the caller asked for; may be smaller than `data`'s capacity when
+    // a larger block was reused from a size class
+    len: usize,
+    pool: &'a BytePool<A>,
 }
 
-impl fmt::Debug for Block<'_> {
+impl<A: Allocator + Clone> fmt::Debug for Block<'_, A> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Block").field("data", &self.data).finish()
     }
 }
 
-impl fmt::Debug for RawBlock {
+impl<A: Allocator> fmt::Debug for RawBlock<A> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "RawBlock({:?})", self.deref())
     }
 }
 
-impl Default for BytePool {
+impl Default for BytePool<Global> {
     fn default() -> Self {
-        BytePool {
-            list: Mutex::new(Vec::new()),
-        }
+        BytePool::with_classes(DEFAULT_CLASSES.iter().map(|&capacity| (capacity, 0)))
     }
 }
 
@@ -81,88 +176,281 @@ fn layout_for_size(size: usize) -> Layout {
     Layout::from_size_align(alloc_size, align).unwrap()
 }
 
-impl BytePool {
-    /// Constructs a new pool.
+impl BytePool<Global> {
+    /// Constructs a new pool, using a default spread of power-of-two size classes
+    /// backed by the global allocator.
     pub fn new() -> Self {
         BytePool::default()
     }
 
+    /// Constructs a new pool with custom size classes, backed by the global allocator.
+    ///
+    /// Each `(capacity, count_hint)` pair describes a bucket of blocks of at least
+    /// `capacity` bytes (rounded up to the next power of two); `count_hint` blocks are
+    /// eagerly allocated and placed on that bucket's free list up front. Allocation
+    /// requests larger than the biggest configured class are satisfied with a one-off,
+    /// exact-size allocation that is not returned to any free list.
+    pub fn with_classes(classes: impl IntoIterator<Item = (usize, usize)>) -> Self {
+        BytePool::with_classes_in(classes, Global)
+    }
+
+    /// Like [`BytePool::with_classes`], but bounds how much memory the pool retains.
+    ///
+    /// See [`Limits`] for what is capped.
+    pub fn with_limits(
+        classes: impl IntoIterator<Item = (usize, usize)>,
+        limits: Limits,
+    ) -> Self {
+        BytePool::with_limits_in(classes, Global, limits)
+    }
+}
+
+impl<A: Allocator + Clone> BytePool<A> {
+    /// Constructs a new pool with custom size classes, backed by `alloc`.
+    ///
+    /// See [`BytePool::with_classes`] for the meaning of `classes`.
+    pub fn with_classes_in(classes: impl IntoIterator<Item = (usize, usize)>, alloc: A) -> Self {
+        BytePool::with_limits_in(classes, alloc, Limits::default())
+    }
+
+    /// Like [`BytePool::with_classes_in`], but bounds how much memory the pool retains.
+    ///
+    /// See [`Limits`] for what is capped.
+    pub fn with_limits_in(
+        classes: impl IntoIterator<Item = (usize, usize)>,
+        alloc: A,
+        limits: Limits,
+    ) -> Self {
+        let mut by_capacity: Vec<(usize, usize)> = Vec::new();
+        for (capacity, count_hint) in classes {
+            assert!(capacity > 0, "size classes must have a positive capacity");
+            let capacity = capacity.next_power_of_two();
+            match by_capacity.iter_mut().find(|(cap, _)| *cap == capacity) {
+                Some((_, hint)) => *hint += count_hint,
+                None => by_capacity.push((capacity, count_hint)),
+            }
+        }
+        by_capacity.sort_unstable_by_key(|(capacity, _)| *capacity);
+
+        let mut retained_bytes = 0usize;
+        let classes = by_capacity
+            .into_iter()
+            .map(|(capacity, count_hint)| {
+                let free = (0..count_hint)
+                    .map(|_| RawBlock::alloc_in(capacity, alloc.clone()))
+                    .collect();
+                retained_bytes += capacity * count_hint;
+                SizeClass {
+                    capacity,
+                    free: Mutex::new(free),
+                }
+            })
+            .collect();
+
+        BytePool {
+            classes,
+            alloc,
+            limits,
+            retained_bytes: AtomicUsize::new(retained_bytes),
+        }
+    }
+
+    /// Returns the index of the smallest size class that can satisfy `size`, or `None`
+    /// if `size` exceeds every configured class.
+    fn class_for_size(&self, size: usize) -> Option<usize> {
+        let idx = self.classes.partition_point(|class| class.capacity < size);
+        if idx < self.classes.len() {
+            Some(idx)
+        } else {
+            None
+        }
+    }
+
+    /// Allocates a new `Block`, which represents a fixed sice byte slice.
+    /// If `Block` is dropped, the memory is returned into the pool's free list for its size
+    /// class; it is deallocated instead if it fits no size class or the pool's [`Limits`]
+    /// are reached.
+    ///
+    /// # Aborts
+    ///
+    /// Aborts the process through [`handle_alloc_error`] if the underlying allocator fails
+    /// to provide memory. Use [`BytePool::try_alloc`] to handle allocation failure instead.
+    pub fn alloc(&self, size: usize) -> Block<'_, A> {
+        self.try_alloc(size)
+            .unwrap_or_else(|err| handle_alloc_error(err.layout))
+    }
+
     /// Allocates a new `Block`, which represents a fixed sice byte slice.
     /// If `Block` is dropped, the memory is _not_ freed, but rather it is returned into the pool.
-    pub fn alloc(&self, size: usize) -> Block<'_> {
+    ///
+    /// Unlike [`BytePool::alloc`], this returns an [`AllocError`] instead of aborting the
+    /// process when the allocator is unable to satisfy the request.
+    pub fn try_alloc(&self, size: usize) -> Result<Block<'_, A>, AllocError> {
         assert!(size > 0, "Can not allocate empty blocks");
 
-        // check the last 4 blocks
-        let mut lock = self.list.lock().unwrap();
-        let end = lock.len();
-        let start = if end > 4 { end - 4 } else { 0 };
+        match self.class_for_size(size) {
+            Some(idx) => {
+                let class = &self.classes[idx];
+                let reused = class.free.lock().unwrap().pop();
+                let data = match reused {
+                    Some(data) => {
+                        self.retained_bytes.fetch_sub(class.capacity, Ordering::Relaxed);
+                        data
+                    }
+                    None => RawBlock::try_alloc_in(class.capacity, self.alloc.clone())?,
+                };
+                Ok(Block::new(data, size, self))
+            }
+            None => {
+                // Bigger than any configured class: a one-off, exact-size allocation.
+                let data = RawBlock::try_alloc_in(size, self.alloc.clone())?;
+                Ok(Block::new(data, size, self))
+            }
+        }
+    }
 
-        for i in start..end {
-            if lock[i].layout.size() == size {
-                // found one, reuse it
-                return Block::new(lock.remove(i), self);
+    /// The number of bytes currently sitting idle in the pool's free lists.
+    pub fn retained_bytes(&self) -> usize {
+        self.retained_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Deallocates every block currently retained in the pool's free lists.
+    ///
+    /// This does not affect blocks that are still checked out as `Block`s; those are
+    /// returned (and, if over the configured [`Limits`], possibly deallocated) as usual
+    /// when they drop.
+    pub fn clear(&self) {
+        for class in &self.classes {
+            class.free.lock().unwrap().clear();
+        }
+        self.retained_bytes.store(0, Ordering::Relaxed);
+    }
+
+    /// Evicts retained blocks, largest size class first, until at most `target_bytes`
+    /// remain idle in the pool's free lists.
+    pub fn shrink_to(&self, target_bytes: usize) {
+        while self.retained_bytes() > target_bytes {
+            if !self.evict_one_from_largest() {
+                break;
             }
         }
-        drop(lock);
+    }
 
-        // allocate a new block
-        let data = RawBlock::alloc(size);
-        Block::new(data, self)
+    /// Pops and deallocates a single block from the largest non-empty size class.
+    /// Returns `false` if every free list is empty.
+    fn evict_one_from_largest(&self) -> bool {
+        for class in self.classes.iter().rev() {
+            let popped = class.free.lock().unwrap().pop();
+            if let Some(block) = popped {
+                self.retained_bytes.fetch_sub(class.capacity, Ordering::Relaxed);
+                drop(block);
+                return true;
+            }
+        }
+        false
     }
 
-    fn push_raw_block(&self, block: RawBlock) {
-        self.list.lock().unwrap().push(block);
+    fn push_raw_block(&self, block: RawBlock<A>) {
+        let idx = match self
+            .classes
+            .binary_search_by_key(&block.layout.size(), |class| class.capacity)
+        {
+            Ok(idx) => idx,
+            // Doesn't match any size class (e.g. it was grown past the largest one via
+            // `Block::realloc`); just let it be deallocated.
+            Err(_) => return,
+        };
+        let class = &self.classes[idx];
+
+        if let Some(max_blocks) = self.limits.max_blocks_per_class {
+            if class.free.lock().unwrap().len() >= max_blocks {
+                return; // let `block` drop and deallocate
+            }
+        }
+
+        if let Some(max_bytes) = self.limits.max_bytes {
+            while self.retained_bytes() + class.capacity > max_bytes {
+                if !self.evict_one_from_largest() {
+                    break;
+                }
+            }
+            if self.retained_bytes() + class.capacity > max_bytes {
+                return; // still over budget even after evicting everything retained
+            }
+        }
+
+        class.free.lock().unwrap().push(block);
+        self.retained_bytes.fetch_add(class.capacity, Ordering::Relaxed);
     }
 }
 
-impl<'a> Drop for Block<'a> {
+impl<A: Allocator + Clone> Drop for Block<'_, A> {
     fn drop(&mut self) {
         let data = mem::ManuallyDrop::into_inner(unsafe { ptr::read(&self.data) });
         self.pool.push_raw_block(data);
     }
 }
 
-impl RawBlock {
-    fn alloc(size: usize) -> Self {
+impl<A: Allocator> RawBlock<A> {
+    fn alloc_in(size: usize, alloc: A) -> Self {
+        Self::try_alloc_in(size, alloc).unwrap_or_else(|err| handle_alloc_error(err.layout))
+    }
+
+    fn try_alloc_in(size: usize, alloc: A) -> Result<Self, AllocError> {
         // TODO: consider caching the layout
         let layout = layout_for_size(size);
         debug_assert!(layout.size() > 0);
 
-        let ptr = unsafe { alloc(layout) };
-        RawBlock {
-            ptr: NonNull::new(ptr).unwrap_or_else(|| handle_alloc_error(layout)),
-            layout,
+        match alloc.allocate(layout) {
+            Ok(ptr) => Ok(RawBlock {
+                ptr: ptr.cast(),
+                layout,
+                alloc,
+            }),
+            Err(_) => Err(AllocError { layout }),
         }
     }
 
-    fn grow(&mut self, new_size: usize) {
+    fn try_grow(&mut self, new_size: usize) -> Result<(), AllocError> {
         // TODO: use grow_in_place once it stablizies and possibly via a flag.
         assert!(new_size > 0);
         let new_layout = Layout::from_size_align(new_size, self.layout.align()).unwrap();
-        let new_ptr = unsafe { realloc(self.ptr.as_mut(), self.layout, new_layout.size()) };
-        self.ptr = NonNull::new(new_ptr).unwrap_or_else(|| handle_alloc_error(self.layout));
-        self.layout = new_layout;
+        let result = unsafe { self.alloc.grow(self.ptr, self.layout, new_layout) };
+        match result {
+            Ok(ptr) => {
+                self.ptr = ptr.cast();
+                self.layout = new_layout;
+                Ok(())
+            }
+            Err(_) => Err(AllocError { layout: new_layout }),
+        }
     }
 
-    fn shrink(&mut self, new_size: usize) {
+    fn try_shrink(&mut self, new_size: usize) -> Result<(), AllocError> {
         // TODO: use shrink_in_place once it stablizies and possibly via a flag.
         assert!(new_size > 0);
         let new_layout = Layout::from_size_align(new_size, self.layout.align()).unwrap();
-        let new_ptr = unsafe { realloc(self.ptr.as_mut(), self.layout, new_layout.size()) };
-        self.ptr = NonNull::new(new_ptr).unwrap_or_else(|| handle_alloc_error(self.layout));
-        self.layout = new_layout;
+        let result = unsafe { self.alloc.shrink(self.ptr, self.layout, new_layout) };
+        match result {
+            Ok(ptr) => {
+                self.ptr = ptr.cast();
+                self.layout = new_layout;
+                Ok(())
+            }
+            Err(_) => Err(AllocError { layout: new_layout }),
+        }
     }
 }
 
-impl Drop for RawBlock {
+impl<A: Allocator> Drop for RawBlock<A> {
     fn drop(&mut self) {
         unsafe {
-            dealloc(self.ptr.as_mut(), self.layout);
+            self.alloc.deallocate(self.ptr, self.layout);
         }
     }
 }
 
-impl Deref for RawBlock {
+impl<A: Allocator> Deref for RawBlock<A> {
     type Target = [u8];
 
     #[inline]
@@ -171,51 +459,244 @@ impl Deref for RawBlock {
     }
 }
 
-impl DerefMut for RawBlock {
+impl<A: Allocator> DerefMut for RawBlock<A> {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
         unsafe { std::slice::from_raw_parts_mut(self.ptr.as_mut(), self.layout.size()) }
     }
 }
 
-impl<'a> Block<'a> {
-    fn new(data: RawBlock, pool: &'a BytePool) -> Self {
+impl<'a, A: Allocator + Clone> Block<'a, A> {
+    fn new(data: RawBlock<A>, len: usize, pool: &'a BytePool<A>) -> Self {
+        debug_assert!(len <= data.layout.size());
         Block {
             data: mem::ManuallyDrop::new(data),
+            len,
             pool,
         }
     }
 
     /// Resizes a block to a new size
+    ///
+    /// # Aborts
+    ///
+    /// Aborts the process through [`handle_alloc_error`] if the underlying allocator fails
+    /// to provide memory. Use [`Block::try_realloc`] to handle allocation failure instead.
     pub fn realloc(&mut self, new_size: usize) {
+        self.try_realloc(new_size)
+            .unwrap_or_else(|err| handle_alloc_error(err.layout));
+    }
+
+    /// Resizes a block to a new size.
+    ///
+    /// Unlike [`Block::realloc`], this returns an [`AllocError`] instead of aborting the
+    /// process when the allocator is unable to satisfy the request.
+    pub fn try_realloc(&mut self, new_size: usize) -> Result<(), AllocError> {
         use std::cmp::Ordering::*;
 
-        match new_size.cmp(&self.size()) {
-            Greater => self.data.grow(new_size),
-            Less => self.data.shrink(new_size),
+        let capacity = self.data.layout.size();
+        match new_size.cmp(&capacity) {
+            Greater => self.data.try_grow(new_size)?,
+            Less => self.data.try_shrink(new_size)?,
             Equal => {}
         }
+        self.len = new_size;
+        Ok(())
     }
 
     /// Returns the amount of bytes this block has.
     pub fn size(&self) -> usize {
-        self.data.layout.size()
+        self.len
+    }
+
+    /// Freezes this block into a [`SharedBlock`], opting into reference-counted, zero-copy
+    /// sharing: the returned handle (and any further handles produced by
+    /// [`SharedBlock::split_off`]/[`SharedBlock::split_to`] or `Clone`) can be passed around
+    /// independently, and the underlying memory is only returned to the pool once the last
+    /// handle is dropped.
+    pub fn freeze(self) -> SharedBlock<'a, A> {
+        let this = mem::ManuallyDrop::new(self);
+        let data = unsafe { ptr::read(&this.data) };
+        let len = this.len;
+        let pool = this.pool;
+
+        let shared = Box::new(Shared {
+            data,
+            pool,
+            count: AtomicUsize::new(1),
+        });
+
+        SharedBlock {
+            shared: NonNull::from(Box::leak(shared)),
+            offset: 0,
+            len,
+        }
     }
 }
 
-impl<'a> Deref for Block<'a> {
+impl<A: Allocator + Clone> Deref for Block<'_, A> {
     type Target = [u8];
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        self.data.deref()
+        &self.data.deref()[..self.len]
     }
 }
 
-impl<'a> DerefMut for Block<'a> {
+impl<A: Allocator + Clone> DerefMut for Block<'_, A> {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.data.deref_mut()
+        &mut self.data.deref_mut()[..self.len]
+    }
+}
+
+/// The backing allocation shared by one or more [`SharedBlock`] handles.
+struct Shared<'a, A: Allocator + Clone> {
+    data: mem::ManuallyDrop<RawBlock<A>>,
+    pool: &'a BytePool<A>,
+    count: AtomicUsize,
+}
+
+impl<A: Allocator + Clone> Drop for Shared<'_, A> {
+    fn drop(&mut self) {
+        let data = mem::ManuallyDrop::into_inner(unsafe { ptr::read(&self.data) });
+        self.pool.push_raw_block(data);
+    }
+}
+
+/// A reference-counted, splittable view into a [`Block`]'s memory.
+///
+/// Created by [`Block::freeze`]. Multiple `SharedBlock` handles can point at disjoint
+/// regions of the same underlying allocation (via [`split_off`](SharedBlock::split_off) /
+/// [`split_to`](SharedBlock::split_to)) or the same region (via `Clone`), without copying
+/// any bytes. The allocation is only returned to the pool once every handle has been
+/// dropped, giving `BytesMut`/`Bytes`-style split ergonomics on top of pool recycling.
+pub struct SharedBlock<'a, A: Allocator + Clone = Global> {
+    shared: NonNull<Shared<'a, A>>,
+    offset: usize,
+    len: usize,
+}
+
+unsafe impl<A: Allocator + Clone + Send + Sync> Send for SharedBlock<'_, A> {}
+unsafe impl<A: Allocator + Clone + Send + Sync> Sync for SharedBlock<'_, A> {}
+
+impl<A: Allocator + Clone> fmt::Debug for SharedBlock<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedBlock")
+            .field("offset", &self.offset)
+            .field("len", &self.len)
+            .field("ref_count", &self.ref_count())
+            .finish()
+    }
+}
+
+impl<A: Allocator + Clone> SharedBlock<'_, A> {
+    /// The number of handles (including this one) that currently share the allocation.
+    pub fn ref_count(&self) -> usize {
+        unsafe { self.shared.as_ref() }.count.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of bytes this handle can see.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if this handle is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Splits the handle in two at `at`: `self` keeps `[0, at)` and the returned handle
+    /// takes `[at, len)`. No bytes are copied; both handles share the same underlying
+    /// allocation, which is returned to the pool once every handle produced from it has
+    /// been dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > self.len()`.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len, "split_off out of bounds");
+        unsafe { self.shared.as_ref() }.count.fetch_add(1, Ordering::Relaxed);
+        let tail = SharedBlock {
+            shared: self.shared,
+            offset: self.offset + at,
+            len: self.len - at,
+        };
+        self.len = at;
+        tail
+    }
+
+    /// Splits the handle in two at `at`: the returned handle takes `[0, at)` and `self`
+    /// keeps `[at, len)`. No bytes are copied; both handles share the same underlying
+    /// allocation, which is returned to the pool once every handle produced from it has
+    /// been dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > self.len()`.
+    pub fn split_to(&mut self, at: usize) -> Self {
+        assert!(at <= self.len, "split_to out of bounds");
+        unsafe { self.shared.as_ref() }.count.fetch_add(1, Ordering::Relaxed);
+        let head = SharedBlock {
+            shared: self.shared,
+            offset: self.offset,
+            len: at,
+        };
+        self.offset += at;
+        self.len -= at;
+        head
+    }
+}
+
+impl<A: Allocator + Clone> Clone for SharedBlock<'_, A> {
+    fn clone(&self) -> Self {
+        unsafe { self.shared.as_ref() }.count.fetch_add(1, Ordering::Relaxed);
+        SharedBlock {
+            shared: self.shared,
+            offset: self.offset,
+            len: self.len,
+        }
+    }
+}
+
+impl<A: Allocator + Clone> Drop for SharedBlock<'_, A> {
+    fn drop(&mut self) {
+        // Mirrors `Arc`'s drop: release on the decrement, acquire-fence before the final
+        // handle actually touches (and frees) the data, so writes from sibling handles
+        // are visible here.
+        if unsafe { self.shared.as_ref() }.count.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        atomic::fence(Ordering::Acquire);
+        drop(unsafe { Box::from_raw(self.shared.as_ptr()) });
+    }
+}
+
+impl<A: Allocator + Clone> Deref for SharedBlock<'_, A> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        let shared = unsafe { self.shared.as_ref() };
+        &shared.data.deref()[self.offset..self.offset + self.len]
+    }
+}
+
+impl<A: Allocator + Clone> DerefMut for SharedBlock<'_, A> {
+    /// # Panics
+    ///
+    /// Panics if other handles (from `Clone`, `split_off`, or `split_to`) are still alive,
+    /// since this allocation may then be viewed from multiple places at once.
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        assert_eq!(
+            self.ref_count(),
+            1,
+            "cannot mutably borrow a SharedBlock with outstanding clones or splits"
+        );
+        let (offset, len) = (self.offset, self.len);
+        let shared = unsafe { self.shared.as_mut() };
+        &mut shared.data.deref_mut()[offset..offset + len]
     }
 }
 
@@ -271,4 +752,157 @@ mod tests {
             assert_eq!(*el, 1);
         }
     }
+
+    #[test]
+    fn try_alloc_ok() {
+        let pool = BytePool::new();
+
+        let mut buf = pool.try_alloc(128).unwrap();
+        assert_eq!(buf.len(), 128);
+        buf[0] = 1;
+
+        buf.try_realloc(256).unwrap();
+        assert_eq!(buf.len(), 256);
+        assert_eq!(buf[0], 1);
+    }
+
+    #[test]
+    fn size_class_best_fit_reuse() {
+        let pool = BytePool::with_classes(vec![(1024, 0)]);
+
+        {
+            let buf = pool.alloc(1000);
+            assert_eq!(buf.len(), 1000);
+        }
+
+        // a slightly different, but nearby, size should still land in the same class
+        // and reuse the block that was just returned.
+        assert_eq!(pool.classes[0].free.lock().unwrap().len(), 1);
+        let buf = pool.alloc(1001);
+        assert_eq!(buf.len(), 1001);
+        assert_eq!(pool.classes[0].free.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn oversized_alloc_is_one_off() {
+        let pool = BytePool::with_classes(vec![(1024, 0)]);
+
+        {
+            let buf = pool.alloc(2048);
+            assert_eq!(buf.len(), 2048);
+        }
+
+        // too big for any class, so it was deallocated rather than retained
+        assert_eq!(pool.classes[0].free.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn custom_allocator() {
+        let pool = BytePool::with_classes_in(vec![(128, 0)], Global);
+
+        let mut buf = pool.alloc(100);
+        assert_eq!(buf.len(), 100);
+        buf[0] = 9;
+        assert_eq!(buf[0], 9);
+    }
+
+    #[test]
+    fn shared_block_split() {
+        let pool = BytePool::new();
+
+        let mut buf = pool.alloc(10);
+        for (i, el) in buf.iter_mut().enumerate() {
+            *el = i as u8;
+        }
+
+        let mut shared = buf.freeze();
+        assert_eq!(shared.ref_count(), 1);
+
+        let tail = shared.split_off(4);
+        assert_eq!(shared.ref_count(), 2);
+        assert_eq!(tail.ref_count(), 2);
+        assert_eq!(&shared[..], &[0, 1, 2, 3]);
+        assert_eq!(&tail[..], &[4, 5, 6, 7, 8, 9]);
+
+        drop(tail);
+        assert_eq!(shared.ref_count(), 1);
+    }
+
+    #[test]
+    fn shared_block_clone_blocks_mutation() {
+        let pool = BytePool::new();
+        let buf = pool.alloc(8);
+
+        let mut shared = buf.freeze();
+        let clone = shared.clone();
+        assert_eq!(shared.ref_count(), 2);
+
+        // with the clone alive, mutable access is refused
+        let blocked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            shared[0] = 1;
+        }));
+        assert!(blocked.is_err());
+
+        drop(clone);
+        assert_eq!(shared.ref_count(), 1);
+        shared[0] = 42;
+        assert_eq!(shared[0], 42);
+    }
+
+    #[test]
+    fn max_blocks_per_class_evicts_on_return() {
+        let pool = BytePool::with_limits(
+            vec![(1024, 0)],
+            Limits {
+                max_bytes: None,
+                max_blocks_per_class: Some(1),
+            },
+        );
+
+        let a = pool.alloc(1000);
+        let b = pool.alloc(1000);
+
+        drop(a);
+        assert_eq!(pool.retained_bytes(), 1024);
+
+        // the class is already at its cap of one retained block, so returning a second
+        // one deallocates it rather than growing the free list.
+        drop(b);
+        assert_eq!(pool.retained_bytes(), 1024);
+    }
+
+    #[test]
+    fn max_bytes_evicts_largest_class_first() {
+        let pool = BytePool::with_limits(
+            vec![(1024, 0), (2048, 0)],
+            Limits {
+                max_bytes: Some(2048),
+                max_blocks_per_class: None,
+            },
+        );
+
+        let big = pool.alloc(2000);
+        drop(big);
+        assert_eq!(pool.retained_bytes(), 2048);
+
+        // returning a small block should evict the larger retained one to make room,
+        // rather than simply refusing to retain the new one.
+        drop(pool.alloc(1000));
+        assert_eq!(pool.retained_bytes(), 1024);
+    }
+
+    #[test]
+    fn clear_and_shrink_to() {
+        let pool = BytePool::with_classes(vec![(1024, 0), (2048, 0)]);
+
+        drop(pool.alloc(1000));
+        drop(pool.alloc(2000));
+        assert_eq!(pool.retained_bytes(), 1024 + 2048);
+
+        pool.shrink_to(1024);
+        assert_eq!(pool.retained_bytes(), 1024);
+
+        pool.clear();
+        assert_eq!(pool.retained_bytes(), 0);
+    }
 }